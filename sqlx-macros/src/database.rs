@@ -0,0 +1,139 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use sqlx_core::database::Database;
+
+/// Extends a concrete [`Database`] with what the query macros need to turn a `Describe`
+/// into generated code: a path to splice into the output (e.g.
+/// `sqlx::query_with::<#db_path, _>`), and a mapping from the database's own type names
+/// (as reported by `DESCRIBE`/introspection) to the Rust type used to represent them.
+pub trait DatabaseExt: Database {
+    fn db_path() -> TokenStream;
+
+    /// Maps a database type name to the Rust type used for a generated `Record` field
+    /// or a `query_as!` output column.
+    fn return_type_for_id(id: &str) -> Option<&'static str>;
+}
+
+#[cfg(feature = "postgres")]
+impl DatabaseExt for sqlx_core::postgres::Postgres {
+    fn db_path() -> TokenStream {
+        quote!(sqlx::postgres::Postgres)
+    }
+
+    fn return_type_for_id(id: &str) -> Option<&'static str> {
+        postgres::return_type_for_id(id)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl DatabaseExt for sqlx_core::mysql::MySql {
+    fn db_path() -> TokenStream {
+        quote!(sqlx::mysql::MySql)
+    }
+
+    fn return_type_for_id(id: &str) -> Option<&'static str> {
+        mysql::return_type_for_id(id)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl DatabaseExt for sqlx_core::sqlite::Sqlite {
+    fn db_path() -> TokenStream {
+        quote!(sqlx::sqlite::Sqlite)
+    }
+
+    fn return_type_for_id(id: &str) -> Option<&'static str> {
+        sqlite::return_type_for_id(id)
+    }
+}
+
+#[cfg(feature = "mssql")]
+impl DatabaseExt for sqlx_core::mssql::Mssql {
+    fn db_path() -> TokenStream {
+        quote!(sqlx::mssql::Mssql)
+    }
+
+    fn return_type_for_id(id: &str) -> Option<&'static str> {
+        mssql::return_type_for_id(id)
+    }
+}
+
+#[cfg(feature = "any")]
+impl DatabaseExt for sqlx_core::any::Any {
+    fn db_path() -> TokenStream {
+        quote!(sqlx::any::Any)
+    }
+
+    fn return_type_for_id(_id: &str) -> Option<&'static str> {
+        // `Any` never describes a query itself (see `query::any`): whichever concrete
+        // driver is enabled alongside it does the describing, so column types are
+        // always resolved through *that* driver's `return_type_for_id`, never this one.
+        None
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    pub fn return_type_for_id(id: &str) -> Option<&'static str> {
+        Some(match id {
+            "BOOL" => "bool",
+            "INT2" => "i16",
+            "INT4" => "i32",
+            "INT8" => "i64",
+            "FLOAT4" => "f32",
+            "FLOAT8" => "f64",
+            "TEXT" | "VARCHAR" | "CHAR" | "NAME" => "String",
+            "BYTEA" => "Vec<u8>",
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(feature = "mysql")]
+mod mysql {
+    pub fn return_type_for_id(id: &str) -> Option<&'static str> {
+        Some(match id {
+            "TINYINT" => "i8",
+            "SMALLINT" => "i16",
+            "INT" | "MEDIUMINT" => "i32",
+            "BIGINT" => "i64",
+            "FLOAT" => "f32",
+            "DOUBLE" => "f64",
+            "VARCHAR" | "CHAR" | "TEXT" => "String",
+            "BLOB" | "VARBINARY" => "Vec<u8>",
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    pub fn return_type_for_id(id: &str) -> Option<&'static str> {
+        Some(match id {
+            "INTEGER" | "INT" => "i64",
+            "REAL" => "f64",
+            "TEXT" => "String",
+            "BLOB" => "Vec<u8>",
+            "BOOLEAN" => "bool",
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(feature = "mssql")]
+mod mssql {
+    pub fn return_type_for_id(id: &str) -> Option<&'static str> {
+        Some(match id {
+            "BIT" => "bool",
+            "TINYINT" => "u8",
+            "SMALLINT" => "i16",
+            "INT" => "i32",
+            "BIGINT" => "i64",
+            "REAL" => "f32",
+            "FLOAT" => "f64",
+            "VARCHAR" | "NVARCHAR" | "CHAR" => "String",
+            "VARBINARY" => "Vec<u8>",
+            _ => return None,
+        })
+    }
+}
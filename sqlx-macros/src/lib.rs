@@ -0,0 +1,6 @@
+mod database;
+pub mod query;
+mod runtime;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T> = std::result::Result<T, Error>;
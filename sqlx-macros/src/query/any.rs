@@ -0,0 +1,134 @@
+use proc_macro2::TokenStream;
+use sqlx_core::any::Any;
+use sqlx_core::connection::Connect;
+
+use crate::query::data::QueryData;
+use crate::query::QueryMacroInput;
+use crate::runtime::block_on;
+use crate::Result;
+
+/// Describes `input.src` using whichever concrete driver feature is enabled alongside
+/// `any`, then hands the result to `expand_with_data_as` with `Any` as the emit target,
+/// so the generated code names `sqlx::Any` and `sqlx::query_with::<sqlx::Any, _>`
+/// instead of a concrete connection type while still getting column/param types from a
+/// real describe.
+///
+/// At least one of `postgres`, `mysql`, `sqlite` or `mssql` must be enabled together
+/// with `any`. If more than one is enabled, `postgres` takes priority, then `mysql`,
+/// then `sqlite`, then `mssql` — the same precedence `query::migrate` uses for its own
+/// driver features, made explicit with `not(feature = ...)` guards.
+///
+/// Like the other scheme arms in `expand_from_db`, this routes through
+/// `describe_provider::DescribeProvider`/`SQLX_DESCRIBE_EXECUTOR` when available,
+/// falling back to connecting directly otherwise — the `any` scheme being the one
+/// sandboxed build hosts can't reach a database from is exactly where that matters most.
+pub fn expand_any(input: QueryMacroInput, db_url: &str) -> Result<TokenStream> {
+    #[cfg(feature = "postgres")]
+    {
+        #[cfg(feature = "offline")]
+        let data = match super::describe_provider::ExternalExecutor::from_env() {
+            Some(executor) => super::describe_provider::DescribeProvider::describe(
+                &executor, db_url, &input.src,
+            )?,
+            None => block_on(async {
+                let mut conn = sqlx_core::postgres::PgConnection::connect(db_url).await?;
+                QueryData::from_db(&mut conn, &input.src).await
+            })?,
+        };
+
+        #[cfg(not(feature = "offline"))]
+        let data = block_on(async {
+            let mut conn = sqlx_core::postgres::PgConnection::connect(db_url).await?;
+            QueryData::from_db(&mut conn, &input.src).await
+        })?;
+
+        return super::expand_with_data_as::<sqlx_core::postgres::Postgres, Any>(input, data);
+    }
+
+    #[cfg(all(feature = "mysql", not(feature = "postgres")))]
+    {
+        #[cfg(feature = "offline")]
+        let data = match super::describe_provider::ExternalExecutor::from_env() {
+            Some(executor) => super::describe_provider::DescribeProvider::describe(
+                &executor, db_url, &input.src,
+            )?,
+            None => block_on(async {
+                let mut conn = sqlx_core::mysql::MySqlConnection::connect(db_url).await?;
+                QueryData::from_db(&mut conn, &input.src).await
+            })?,
+        };
+
+        #[cfg(not(feature = "offline"))]
+        let data = block_on(async {
+            let mut conn = sqlx_core::mysql::MySqlConnection::connect(db_url).await?;
+            QueryData::from_db(&mut conn, &input.src).await
+        })?;
+
+        return super::expand_with_data_as::<sqlx_core::mysql::MySql, Any>(input, data);
+    }
+
+    #[cfg(all(
+        feature = "sqlite",
+        not(any(feature = "postgres", feature = "mysql"))
+    ))]
+    {
+        #[cfg(feature = "offline")]
+        let data = match super::describe_provider::ExternalExecutor::from_env() {
+            Some(executor) => super::describe_provider::DescribeProvider::describe(
+                &executor, db_url, &input.src,
+            )?,
+            None => block_on(async {
+                let mut conn = sqlx_core::sqlite::SqliteConnection::connect(db_url).await?;
+                QueryData::from_db(&mut conn, &input.src).await
+            })?,
+        };
+
+        #[cfg(not(feature = "offline"))]
+        let data = block_on(async {
+            let mut conn = sqlx_core::sqlite::SqliteConnection::connect(db_url).await?;
+            QueryData::from_db(&mut conn, &input.src).await
+        })?;
+
+        return super::expand_with_data_as::<sqlx_core::sqlite::Sqlite, Any>(input, data);
+    }
+
+    #[cfg(all(
+        feature = "mssql",
+        not(any(feature = "postgres", feature = "mysql", feature = "sqlite"))
+    ))]
+    {
+        #[cfg(feature = "offline")]
+        let data = match super::describe_provider::ExternalExecutor::from_env() {
+            Some(executor) => super::describe_provider::DescribeProvider::describe(
+                &executor, db_url, &input.src,
+            )?,
+            None => block_on(async {
+                let mut conn = sqlx_core::mssql::MssqlConnection::connect(db_url).await?;
+                QueryData::from_db(&mut conn, &input.src).await
+            })?,
+        };
+
+        #[cfg(not(feature = "offline"))]
+        let data = block_on(async {
+            let mut conn = sqlx_core::mssql::MssqlConnection::connect(db_url).await?;
+            QueryData::from_db(&mut conn, &input.src).await
+        })?;
+
+        return super::expand_with_data_as::<sqlx_core::mssql::Mssql, Any>(input, data);
+    }
+
+    #[cfg(not(any(
+        feature = "postgres",
+        feature = "mysql",
+        feature = "sqlite",
+        feature = "mssql"
+    )))]
+    {
+        let _ = (input, db_url);
+        Err(
+            "the `any` feature requires at least one of the `postgres`, `mysql`, `sqlite` \
+             or `mssql` features to also be enabled"
+                .into(),
+        )
+    }
+}
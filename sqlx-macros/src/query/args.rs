@@ -0,0 +1,30 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::database::DatabaseExt;
+use crate::query::input::QueryMacroInput;
+use crate::Result;
+
+/// Builds the `query_args` binding used by the generated call: an `Arguments` value of
+/// `DB`'s concrete type with each of the macro's `args = [...]` expressions pushed onto
+/// it. `DB` is the database the *generated code* targets (`sqlx::Any` for `query!()`
+/// against the `any` scheme) — independent of whichever driver actually described the
+/// query, since building `Arguments` doesn't need the column/param type information.
+pub fn quote_args<DB: DatabaseExt>(input: &QueryMacroInput, params_len: usize) -> Result<TokenStream> {
+    if input.arg_exprs.len() != params_len {
+        return Err(format!(
+            "expected {} parameters, got {}",
+            params_len,
+            input.arg_exprs.len()
+        )
+        .into());
+    }
+
+    let db_path = DB::db_path();
+    let arg_exprs = &input.arg_exprs;
+
+    Ok(quote! {
+        let mut query_args = <#db_path as sqlx::Database>::Arguments::default();
+        #(query_args.add(#arg_exprs);)*
+    })
+}
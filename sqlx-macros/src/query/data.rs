@@ -0,0 +1,134 @@
+use proc_macro2::Span;
+use sqlx_core::database::Database;
+use sqlx_core::describe::Describe;
+use sqlx_core::executor::Executor;
+
+use crate::database::DatabaseExt;
+use crate::Result;
+
+/// The result of describing a query, paired with the query text itself. `save_in` does
+/// not write under this text: it takes the cache key (optionally namespaced by
+/// `database_url_env`, see `QueryMacroInput::cache_key`) as its own argument.
+pub struct QueryData<DB: Database> {
+    pub(super) query: String,
+    pub(super) describe: Describe<DB>,
+}
+
+impl<DB: DatabaseExt> QueryData<DB> {
+    /// Describe `query` by running it against a live connection.
+    pub async fn from_db(conn: &mut impl Executor<Database = DB>, query: &str) -> Result<Self> {
+        Ok(QueryData {
+            query: query.to_string(),
+            describe: conn.describe(query).await?,
+        })
+    }
+
+    /// Build a `QueryData` from an already-obtained `Describe`, e.g. one returned by an
+    /// external describe executor ([`crate::query::describe_provider`]). `query` is the
+    /// query text; the offline cache key is passed to `save_in` separately.
+    pub fn from_describe(query: &str, describe: Describe<DB>) -> Self {
+        QueryData {
+            query: query.to_string(),
+            describe,
+        }
+    }
+
+    /// `emit_db_name` is `EmitDB::NAME` from the call site (see
+    /// `query::expand_with_data_as`) — the database the *generated code* targets, which
+    /// is `DB::NAME` itself except for `query!()` against the `any` scheme, where `DB`
+    /// is whichever concrete driver described the query but `emit_db_name` is `"Any"`.
+    /// Recording it lets offline replay (`expand_from_file`) reconstruct that the query
+    /// was originally emitted against `Any` even though it was described against a
+    /// concrete driver, instead of silently collapsing back to that concrete driver.
+    #[cfg(feature = "offline")]
+    pub fn save_in(
+        &self,
+        dir: impl AsRef<std::path::Path>,
+        cache_key: &str,
+        emit_db_name: &str,
+        _src_span: Span,
+    ) -> Result<()>
+    where
+        Describe<DB>: serde::Serialize,
+    {
+        let path = dir.as_ref().join(offline::hash_cache_key(cache_key));
+
+        let data = offline::DynQueryData {
+            db_name: DB::NAME.to_string(),
+            emit_db_name: if emit_db_name == DB::NAME {
+                None
+            } else {
+                Some(emit_db_name.to_string())
+            },
+            query: cache_key.to_string(),
+            describe: serde_json::to_value(&self.describe)?,
+        };
+
+        std::fs::write(path, serde_json::to_string_pretty(&data)?)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "offline")]
+pub mod offline {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use std::path::Path;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::database::DatabaseExt;
+    use crate::query::data::QueryData;
+    use crate::Result;
+
+    /// A recorded, type-erased `Describe` as saved to `sqlx-data.json`: one entry per
+    /// distinct cache key (see `QueryMacroInput::cache_key`), keyed by a hash of that
+    /// string. Each lookup (`from_data_file`) parses the whole file.
+    #[derive(Serialize, Deserialize)]
+    pub struct DynQueryData {
+        pub db_name: String,
+        /// `None` means "same as `db_name`" (every query except ones originally
+        /// described via `query::any`, and every file recorded before this field
+        /// existed), so old `sqlx-data.json` files keep loading unchanged.
+        #[serde(default)]
+        pub emit_db_name: Option<String>,
+        pub query: String,
+        pub describe: serde_json::Value,
+    }
+
+    impl DynQueryData {
+        pub fn from_data_file(file: impl AsRef<Path>, cache_key: &str) -> Result<Self> {
+            let data_file: std::collections::HashMap<String, DynQueryData> =
+                serde_json::from_slice(&std::fs::read(file)?)?;
+
+            data_file
+                .get(&hash_cache_key(cache_key))
+                .map(|entry| DynQueryData {
+                    db_name: entry.db_name.clone(),
+                    emit_db_name: entry.emit_db_name.clone(),
+                    query: entry.query.clone(),
+                    describe: entry.describe.clone(),
+                })
+                .ok_or_else(|| format!("no recorded query data found for {:?}", cache_key).into())
+        }
+    }
+
+    impl<DB: DatabaseExt> QueryData<DB>
+    where
+        sqlx_core::describe::Describe<DB>: serde::de::DeserializeOwned,
+    {
+        pub fn from_dyn_data(data: DynQueryData) -> Result<Self> {
+            Ok(QueryData {
+                query: data.query,
+                describe: serde_json::from_value(data.describe)?,
+            })
+        }
+    }
+
+    pub(super) fn hash_cache_key(cache_key: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        cache_key.hash(&mut hasher);
+        format!("{:x}", hasher.finish())
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+use sqlx_core::database::Database;
+use sqlx_core::describe::Describe;
+
+use crate::query::data::QueryData;
+use crate::Result;
+
+/// Abstracts "given a SQL string, return a `Describe<DB>`" so `expand_from_db` isn't
+/// hardwired to opening the connection itself. The default behavior (connecting
+/// directly, inline in each scheme arm of `expand_from_db`) is left as-is; this trait's
+/// only current implementor, [`ExternalExecutor`], instead delegates to a helper process
+/// selected via `SQLX_DESCRIBE_EXECUTOR`, speaking a small JSON protocol over its stdio.
+///
+/// This is for build hosts that can't open a connection to the database themselves
+/// (sandboxed builds, databases reachable only through a proxy) but can still shell out
+/// to something that can.
+pub trait DescribeProvider<DB: Database>
+where
+    Describe<DB>: serde::de::DeserializeOwned,
+{
+    fn describe(&self, db_url: &str, query: &str) -> Result<QueryData<DB>>;
+}
+
+/// Delegates describing to an external process configured via `SQLX_DESCRIBE_EXECUTOR`.
+pub struct ExternalExecutor {
+    path: String,
+}
+
+impl ExternalExecutor {
+    /// Returns `Some` if `SQLX_DESCRIBE_EXECUTOR` is set in the environment.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("SQLX_DESCRIBE_EXECUTOR")
+            .ok()
+            .map(|path| ExternalExecutor { path })
+    }
+}
+
+#[derive(Serialize)]
+struct DescribeRequest<'a> {
+    db_url: &'a str,
+    query: &'a str,
+}
+
+impl<DB: Database> DescribeProvider<DB> for ExternalExecutor
+where
+    Describe<DB>: serde::de::DeserializeOwned,
+{
+    fn describe(&self, db_url: &str, query: &str) -> Result<QueryData<DB>> {
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("failed to spawn describe executor {:?}: {}", self.path, e))?;
+
+        // Write the request on its own thread: the executor may start writing its
+        // response before it has finished reading stdin, and with both ends piped that
+        // can deadlock if we write synchronously here before ever draining stdout.
+        let mut stdin = child.stdin.take().expect("stdin was piped");
+        let request = DescribeRequest { db_url, query };
+        let request_json = serde_json::to_vec(&request)?;
+        let writer = std::thread::spawn(move || stdin.write_all(&request_json));
+
+        let output = child.wait_with_output()?;
+        writer
+            .join()
+            .map_err(|_| "describe executor stdin writer thread panicked")?
+            .map_err(|e| format!("failed to write request to describe executor: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "describe executor {:?} exited with {}",
+                self.path, output.status
+            )
+            .into());
+        }
+
+        let describe: Describe<DB> = serde_json::from_slice(&output.stdout).map_err(|e| {
+            format!(
+                "describe executor {:?} returned an invalid response: {}",
+                self.path, e
+            )
+        })?;
+
+        Ok(QueryData::from_describe(query, describe))
+    }
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::env;
 use std::fs;
 
@@ -19,6 +20,11 @@ pub struct QueryMacroInput {
     pub(super) arg_exprs: Vec<Expr>,
 
     pub(super) checked: bool,
+
+    /// Overrides which environment variable `expand_input` reads the database URL from,
+    /// falling back to `DATABASE_URL` if unset. Lets a single crate point different
+    /// `query!()` invocations at different databases (e.g. an app DB and an analytics DB).
+    pub(super) database_url_env: Option<String>,
 }
 
 enum QuerySrc {
@@ -37,6 +43,7 @@ impl Parse for QueryMacroInput {
         let mut args: Option<Vec<Expr>> = None;
         let mut record_type = RecordType::Generated;
         let mut checked = true;
+        let mut database_url_env = None;
 
         let mut expect_comma = false;
 
@@ -67,6 +74,9 @@ impl Parse for QueryMacroInput {
             } else if key == "checked" {
                 let lit_bool = input.parse::<LitBool>()?;
                 checked = lit_bool.value;
+            } else if key == "database_url_env" {
+                let lit_str = input.parse::<LitStr>()?;
+                database_url_env = Some(lit_str.value());
             } else {
                 let message = format!("unexpected input key: {}", key);
                 return Err(syn::Error::new_spanned(key, message));
@@ -86,10 +96,24 @@ impl Parse for QueryMacroInput {
             record_type,
             arg_exprs,
             checked,
+            database_url_env,
         })
     }
 }
 
+impl QueryMacroInput {
+    /// The key this query is saved and looked up under in the offline `sqlx-data.json`
+    /// cache. Namespaced by `database_url_env` when set, so the same SQL text pointed
+    /// at two different databases doesn't collide; both `expand_from_file`'s lookup and
+    /// `QueryData::save_in`'s write go through this so they always agree.
+    pub(super) fn cache_key(&self) -> Cow<'_, str> {
+        match &self.database_url_env {
+            Some(env_name) => Cow::Owned(format!("{}::{}", env_name, self.src)),
+            None => Cow::Borrowed(self.src.as_str()),
+        }
+    }
+}
+
 impl QuerySrc {
     /// If the query source is a file, read it to a string. Otherwise return the query string.
     fn resolve(self, source_span: Span) -> syn::Result<String> {
@@ -147,3 +171,38 @@ fn read_file_src(source: &str, source_span: Span) -> syn::Result<String> {
         )
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input_with(src: &str, database_url_env: Option<&str>) -> QueryMacroInput {
+        QueryMacroInput {
+            src: src.to_string(),
+            src_span: Span::call_site(),
+            record_type: RecordType::Generated,
+            arg_exprs: Vec::new(),
+            checked: true,
+            database_url_env: database_url_env.map(String::from),
+        }
+    }
+
+    #[test]
+    fn cache_key_defaults_to_plain_source() {
+        let input = input_with("select 1", None);
+        assert_eq!(input.cache_key(), "select 1");
+    }
+
+    #[test]
+    fn cache_key_is_namespaced_by_database_url_env() {
+        let input = input_with("select 1", Some("ANALYTICS_DATABASE_URL"));
+        assert_eq!(input.cache_key(), "ANALYTICS_DATABASE_URL::select 1");
+    }
+
+    #[test]
+    fn cache_key_distinguishes_different_envs_for_the_same_source() {
+        let app = input_with("select 1", Some("APP_DATABASE_URL"));
+        let analytics = input_with("select 1", Some("ANALYTICS_DATABASE_URL"));
+        assert_ne!(app.cache_key(), analytics.cache_key());
+    }
+}
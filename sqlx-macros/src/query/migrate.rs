@@ -0,0 +1,213 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use once_cell::sync::OnceCell;
+use proc_macro2::TokenStream;
+use url::Url;
+
+use crate::database::DatabaseExt;
+use crate::query::data::QueryData;
+use crate::query::QueryMacroInput;
+use crate::runtime::block_on;
+use crate::Result;
+
+// NOTE: the `sqlx_tmp_<pid>` schemas/databases created below are never dropped. There's
+// no reliable cross-platform process-exit hook available here — macro expansion runs
+// inside rustc's own process rather than a `main()` we control, so there's no destructor
+// to hang a `DROP SCHEMA`/`DROP DATABASE` off of without pulling in a new dependency for
+// it. If `SQLX_MIGRATIONS_DATABASE_URL` points at a shared, long-lived server,
+// periodically clean up with `DROP SCHEMA sqlx_tmp_1234 CASCADE` (Postgres) or
+// `DROP DATABASE sqlx_tmp_1234` (MySQL).
+
+/// Expands `query!()` by describing `input.src` against an ephemeral database seeded
+/// from the ordered `.sql` files in `migrations_dir`, instead of a pre-provisioned one.
+/// Exactly one of `postgres`, `mysql` or `sqlite` is expected to be enabled; if more than
+/// one is, `postgres` takes priority, then `mysql`, then `sqlite` (the same precedence
+/// `query::any` uses when more than one driver feature is on).
+///
+/// SQLite runs fully in-memory (`sqlite::memory:`) and needs nothing else. Postgres and
+/// MySQL still need a reachable server to carve a throwaway schema/database out of,
+/// pointed to by `SQLX_MIGRATIONS_DATABASE_URL`.
+///
+/// The connection (and the migrations applied to it) is created once per
+/// macro-expansion process and reused for every subsequent `query!()` call, so hundreds
+/// of invocations don't each pay to re-run migrations from scratch. This makes
+/// `cargo sqlx prepare` reproducible from migrations alone.
+pub fn expand_with_migrations(input: QueryMacroInput, migrations_dir: &str) -> Result<TokenStream> {
+    #[cfg(feature = "postgres")]
+    {
+        let data = describe_postgres(migrations_dir, &input.src)?;
+        return super::expand_with_data(input, data);
+    }
+
+    #[cfg(all(feature = "mysql", not(feature = "postgres")))]
+    {
+        let data = describe_mysql(migrations_dir, &input.src)?;
+        return super::expand_with_data(input, data);
+    }
+
+    #[cfg(all(feature = "sqlite", not(any(feature = "postgres", feature = "mysql"))))]
+    {
+        let data = describe_sqlite(migrations_dir, &input.src)?;
+        return super::expand_with_data(input, data);
+    }
+
+    #[cfg(not(any(feature = "postgres", feature = "mysql", feature = "sqlite")))]
+    {
+        let _ = migrations_dir;
+        let _ = input;
+        Err("SQLX_MIGRATIONS_DIR requires one of the `postgres`, `mysql` or `sqlite` \
+             features to provision an ephemeral database"
+            .into())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+static SQLITE_EPHEMERAL_CONN: OnceCell<Mutex<Option<sqlx_core::sqlite::SqliteConnection>>> =
+    OnceCell::new();
+
+#[cfg(feature = "sqlite")]
+fn describe_sqlite(migrations_dir: &str, query: &str) -> Result<QueryData<sqlx_core::sqlite::Sqlite>> {
+    use sqlx_core::connection::Connect;
+
+    let conn_cell = SQLITE_EPHEMERAL_CONN.get_or_try_init(|| {
+        block_on(async {
+            let mut conn = sqlx_core::sqlite::SqliteConnection::connect("sqlite::memory:").await?;
+            apply_migrations(&mut conn, migrations_dir).await?;
+            Result::Ok(conn)
+        })
+        .map(|conn| Mutex::new(Some(conn)))
+    })?;
+
+    checkout_and_describe(conn_cell, query)
+}
+
+#[cfg(feature = "postgres")]
+static POSTGRES_EPHEMERAL_CONN: OnceCell<Mutex<Option<sqlx_core::postgres::PgConnection>>> =
+    OnceCell::new();
+
+#[cfg(feature = "postgres")]
+fn describe_postgres(
+    migrations_dir: &str,
+    query: &str,
+) -> Result<QueryData<sqlx_core::postgres::Postgres>> {
+    use sqlx_core::connection::Connect;
+    use sqlx_core::executor::Executor;
+
+    let conn_cell = POSTGRES_EPHEMERAL_CONN.get_or_try_init(|| {
+        block_on(async {
+            let admin_url = std::env::var("SQLX_MIGRATIONS_DATABASE_URL").map_err(|_| {
+                "SQLX_MIGRATIONS_DATABASE_URL must point at a reachable Postgres server to \
+                 provision the ephemeral migrations schema"
+            })?;
+
+            let schema = format!("sqlx_tmp_{}", std::process::id());
+
+            let mut admin_conn = sqlx_core::postgres::PgConnection::connect(&admin_url).await?;
+            admin_conn
+                .execute(&*format!("CREATE SCHEMA IF NOT EXISTS {}", schema))
+                .await?;
+
+            let mut conn = sqlx_core::postgres::PgConnection::connect(&admin_url).await?;
+            conn.execute(&*format!("SET search_path = {}", schema)).await?;
+
+            apply_migrations(&mut conn, migrations_dir).await?;
+            Result::Ok(conn)
+        })
+        .map(|conn| Mutex::new(Some(conn)))
+    })?;
+
+    checkout_and_describe(conn_cell, query)
+}
+
+#[cfg(feature = "mysql")]
+static MYSQL_EPHEMERAL_CONN: OnceCell<Mutex<Option<sqlx_core::mysql::MySqlConnection>>> =
+    OnceCell::new();
+
+#[cfg(feature = "mysql")]
+fn describe_mysql(migrations_dir: &str, query: &str) -> Result<QueryData<sqlx_core::mysql::MySql>> {
+    use sqlx_core::connection::Connect;
+    use sqlx_core::executor::Executor;
+
+    let conn_cell = MYSQL_EPHEMERAL_CONN.get_or_try_init(|| {
+        block_on(async {
+            let admin_url = std::env::var("SQLX_MIGRATIONS_DATABASE_URL").map_err(|_| {
+                "SQLX_MIGRATIONS_DATABASE_URL must point at a reachable MySQL server to \
+                 provision the ephemeral migrations database"
+            })?;
+
+            let database = format!("sqlx_tmp_{}", std::process::id());
+
+            let mut admin_conn = sqlx_core::mysql::MySqlConnection::connect(&admin_url).await?;
+            admin_conn
+                .execute(&*format!("CREATE DATABASE IF NOT EXISTS {}", database))
+                .await?;
+
+            // Rewrite just the path segment rather than string-concatenating: `admin_url`
+            // is a normal admin connection string and so almost always already points at
+            // a database (e.g. `mysql://root@localhost/mysql`), which raw concatenation
+            // would turn into a malformed `.../mysql/sqlx_tmp_1234`.
+            let mut conn_url = Url::parse(&admin_url)?;
+            conn_url.set_path(&database);
+            let mut conn = sqlx_core::mysql::MySqlConnection::connect(conn_url.as_str()).await?;
+
+            apply_migrations(&mut conn, migrations_dir).await?;
+            Result::Ok(conn)
+        })
+        .map(|conn| Mutex::new(Some(conn)))
+    })?;
+
+    checkout_and_describe(conn_cell, query)
+}
+
+async fn apply_migrations(conn: &mut impl sqlx_core::executor::Executor, migrations_dir: &str) -> Result<()> {
+    let mut migrations: Vec<_> = std::fs::read_dir(migrations_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "sql"))
+        .collect();
+
+    // Sort on the leading version number (as sqlx's real migrator does), not on the
+    // path itself: a lexicographic sort would run "10_add_col.sql" before "2_init.sql".
+    migrations.sort_by_key(|path| migration_version(path).unwrap_or(u64::MAX));
+
+    for path in migrations {
+        let sql = std::fs::read_to_string(&path)?;
+        conn.execute(&*sql).await?;
+    }
+
+    Ok(())
+}
+
+fn migration_version(path: &Path) -> Option<u64> {
+    path.file_stem()?.to_str()?.split('_').next()?.parse().ok()
+}
+
+/// Checks the connection out from behind the lock, runs the (async) describe with the
+/// guard already dropped, then checks it back in. Holding a `std::sync::MutexGuard`
+/// across an `.await` trips `clippy::await_holding_lock`; this check-out/check-in dance
+/// keeps the critical section synchronous while still giving one `query!()` expansion
+/// at a time exclusive use of the single shared ephemeral connection. An overlapping
+/// expansion that finds it checked out gets an error rather than waiting for it.
+fn checkout_and_describe<DB>(
+    conn_cell: &Mutex<Option<DB::Connection>>,
+    query: &str,
+) -> Result<QueryData<DB>>
+where
+    DB: DatabaseExt,
+    DB::Connection: sqlx_core::executor::Executor<Database = DB>,
+{
+    let mut conn = conn_cell
+        .lock()
+        .map_err(|_| "ephemeral migration connection poisoned")?
+        .take()
+        .ok_or("ephemeral migration connection is already in use")?;
+
+    let result = block_on(QueryData::from_db(&mut conn, query));
+
+    if let Ok(mut guard) = conn_cell.lock() {
+        *guard = Some(conn);
+    }
+
+    result
+}
@@ -1,7 +1,6 @@
-use std::borrow::Cow;
 use std::env;
 
-use proc_macro2::{Span, TokenStream};
+use proc_macro2::TokenStream;
 use syn::Type;
 use url::Url;
 
@@ -17,9 +16,16 @@ use crate::query::input::RecordType;
 use crate::runtime::block_on;
 
 mod args;
+#[cfg(feature = "any")]
+mod any;
 mod data;
+#[cfg(feature = "offline")]
+mod describe_provider;
 mod input;
+mod migrate;
 mod output;
+#[cfg(feature = "offline")]
+mod schema;
 
 pub fn expand_input(input: QueryMacroInput) -> crate::Result<TokenStream> {
     let manifest_dir =
@@ -33,8 +39,16 @@ pub fn expand_input(input: QueryMacroInput) -> crate::Result<TokenStream> {
             .map_err(|e| format!("failed to load environment from {:?}, {}", env_path, e))?
     }
 
+    // `SQLX_MIGRATIONS_DIR` takes priority over any `DATABASE_URL`: describe against an
+    // ephemeral database built fresh from migrations rather than a pre-provisioned one.
+    if let Ok(migrations_dir) = env::var("SQLX_MIGRATIONS_DIR") {
+        return migrate::expand_with_migrations(input, &migrations_dir);
+    }
+
     // if `dotenv` wasn't initialized by the above we make sure to do it here
-    match dotenv::var("DATABASE_URL").ok() {
+    let db_url_env = input.database_url_env.as_deref().unwrap_or("DATABASE_URL");
+
+    match dotenv::var(db_url_env).ok() {
         Some(db_url) => expand_from_db(input, &db_url),
 
         #[cfg(feature = "offline")]
@@ -59,12 +73,30 @@ pub fn expand_input(input: QueryMacroInput) -> crate::Result<TokenStream> {
 
 #[allow(unused_variables)]
 fn expand_from_db(input: QueryMacroInput, db_url: &str) -> crate::Result<TokenStream> {
-    // FIXME: Introduce [sqlx::any::AnyConnection] and [sqlx::any::AnyDatabase] to support
-    //        runtime determinism here
-
     let db_url = Url::parse(db_url)?;
     match db_url.scheme() {
-        #[cfg(feature = "postgres")]
+        #[cfg(feature = "any")]
+        "any" => any::expand_any(input, db_url.as_str()),
+
+        #[cfg(not(feature = "any"))]
+        "any" => Err(format!("database URL has the scheme of a runtime-determined database but the `any` feature is not enabled").into()),
+
+        #[cfg(all(feature = "postgres", feature = "offline"))]
+        "postgres" | "postgresql" => {
+            let data = match describe_provider::ExternalExecutor::from_env() {
+                Some(executor) => {
+                    describe_provider::DescribeProvider::describe(&executor, db_url.as_str(), &input.src)?
+                }
+                None => block_on(async {
+                    let mut conn = sqlx_core::postgres::PgConnection::connect(db_url.as_str()).await?;
+                    QueryData::from_db(&mut conn, &input.src).await
+                })?,
+            };
+
+            expand_with_data(input, data)
+        },
+
+        #[cfg(all(feature = "postgres", not(feature = "offline")))]
         "postgres" | "postgresql" => {
             let data = block_on(async {
                 let mut conn = sqlx_core::postgres::PgConnection::connect(db_url.as_str()).await?;
@@ -77,7 +109,22 @@ fn expand_from_db(input: QueryMacroInput, db_url: &str) -> crate::Result<TokenSt
         #[cfg(not(feature = "postgres"))]
         "postgres" | "postgresql" => Err(format!("database URL has the scheme of a PostgreSQL database but the `postgres` feature is not enabled").into()),
 
-        #[cfg(feature = "mssql")]
+        #[cfg(all(feature = "mssql", feature = "offline"))]
+        "mssql" | "sqlserver" => {
+            let data = match describe_provider::ExternalExecutor::from_env() {
+                Some(executor) => {
+                    describe_provider::DescribeProvider::describe(&executor, db_url.as_str(), &input.src)?
+                }
+                None => block_on(async {
+                    let mut conn = sqlx_core::mssql::MssqlConnection::connect(db_url.as_str()).await?;
+                    QueryData::from_db(&mut conn, &input.src).await
+                })?,
+            };
+
+            expand_with_data(input, data)
+        },
+
+        #[cfg(all(feature = "mssql", not(feature = "offline")))]
         "mssql" | "sqlserver" => {
             let data = block_on(async {
                 let mut conn = sqlx_core::mssql::MssqlConnection::connect(db_url.as_str()).await?;
@@ -90,7 +137,22 @@ fn expand_from_db(input: QueryMacroInput, db_url: &str) -> crate::Result<TokenSt
         #[cfg(not(feature = "mssql"))]
         "mssql" | "sqlserver" => Err(format!("database URL has the scheme of a MSSQL database but the `mssql` feature is not enabled").into()),
 
-        #[cfg(feature = "mysql")]
+        #[cfg(all(feature = "mysql", feature = "offline"))]
+        "mysql" | "mariadb" => {
+            let data = match describe_provider::ExternalExecutor::from_env() {
+                Some(executor) => {
+                    describe_provider::DescribeProvider::describe(&executor, db_url.as_str(), &input.src)?
+                }
+                None => block_on(async {
+                    let mut conn = sqlx_core::mysql::MySqlConnection::connect(db_url.as_str()).await?;
+                    QueryData::from_db(&mut conn, &input.src).await
+                })?,
+            };
+
+            expand_with_data(input, data)
+        },
+
+        #[cfg(all(feature = "mysql", not(feature = "offline")))]
         "mysql" | "mariadb" => {
             let data = block_on(async {
                 let mut conn = sqlx_core::mysql::MySqlConnection::connect(db_url.as_str()).await?;
@@ -103,7 +165,22 @@ fn expand_from_db(input: QueryMacroInput, db_url: &str) -> crate::Result<TokenSt
         #[cfg(not(feature = "mysql"))]
         "mysql" | "mariadb" => Err(format!("database URL has the scheme of a MySQL/MariaDB database but the `mysql` feature is not enabled").into()),
 
-        #[cfg(feature = "sqlite")]
+        #[cfg(all(feature = "sqlite", feature = "offline"))]
+        "sqlite" => {
+            let data = match describe_provider::ExternalExecutor::from_env() {
+                Some(executor) => {
+                    describe_provider::DescribeProvider::describe(&executor, db_url.as_str(), &input.src)?
+                }
+                None => block_on(async {
+                    let mut conn = sqlx_core::sqlite::SqliteConnection::connect(db_url.as_str()).await?;
+                    QueryData::from_db(&mut conn, &input.src).await
+                })?,
+            };
+
+            expand_with_data(input, data)
+        },
+
+        #[cfg(all(feature = "sqlite", not(feature = "offline")))]
         "sqlite" => {
             let data = block_on(async {
                 let mut conn = sqlx_core::sqlite::SqliteConnection::connect(db_url.as_str()).await?;
@@ -127,25 +204,45 @@ pub fn expand_from_file(
 ) -> crate::Result<TokenStream> {
     use data::offline::DynQueryData;
 
-    let query_data = DynQueryData::from_data_file(file, &input.src)?;
+    // Queries recorded against a non-default `database_url_env` are keyed separately so
+    // the same `source` pointed at two different databases doesn't collide in the cache.
+    // `save_in` (below) hashes the exact same key, so a query saved under one
+    // `database_url_env` is always found under that same env when looked up again.
+    let cache_key = input.cache_key();
+
+    let query_data = match DynQueryData::from_data_file(file, &cache_key) {
+        Ok(query_data) => query_data,
+
+        // No recording for this query yet; fall back to resolving it against a
+        // committed schema snapshot (`cargo sqlx prepare --schema`) instead of failing.
+        Err(e) => {
+            let manifest_dir = env::var("CARGO_MANIFEST_DIR")
+                .map_err(|_| "`CARGO_MANIFEST_DIR` must be set")?;
+            let snapshot_path = schema::default_snapshot_path(&manifest_dir);
+
+            return if snapshot_path.exists() {
+                let snapshot = schema::SchemaSnapshot::load(snapshot_path)?;
+                schema::expand_from_snapshot(input, &snapshot)
+            } else {
+                Err(e)
+            };
+        }
+    };
     assert!(!query_data.db_name.is_empty());
 
     match &*query_data.db_name {
         #[cfg(feature = "postgres")]
-        sqlx_core::postgres::Postgres::NAME => expand_with_data(
-            input,
-            QueryData::<sqlx_core::postgres::Postgres>::from_dyn_data(query_data)?,
-        ),
+        sqlx_core::postgres::Postgres::NAME => {
+            dispatch_dyn_data::<sqlx_core::postgres::Postgres>(input, query_data)
+        }
         #[cfg(feature = "mysql")]
-        sqlx_core::mysql::MySql::NAME => expand_with_data(
-            input,
-            QueryData::<sqlx_core::mysql::MySql>::from_dyn_data(query_data)?,
-        ),
+        sqlx_core::mysql::MySql::NAME => {
+            dispatch_dyn_data::<sqlx_core::mysql::MySql>(input, query_data)
+        }
         #[cfg(feature = "sqlite")]
-        sqlx_core::sqlite::Sqlite::NAME => expand_with_data(
-            input,
-            QueryData::<sqlx_core::sqlite::Sqlite>::from_dyn_data(query_data)?,
-        ),
+        sqlx_core::sqlite::Sqlite::NAME => {
+            dispatch_dyn_data::<sqlx_core::sqlite::Sqlite>(input, query_data)
+        }
         _ => Err(format!(
             "found query data for {} but the feature for that database was not enabled",
             query_data.db_name
@@ -154,6 +251,40 @@ pub fn expand_from_file(
     }
 }
 
+/// Rebuilds `QueryData<DescribeDB>` from the recorded entry, then dispatches to
+/// whichever `EmitDB` it was originally recorded against — `DescribeDB` itself for
+/// ordinary queries, or `sqlx::Any` for a query originally described via `query::any`
+/// (see `data::offline::DynQueryData::emit_db_name`). Without this, a `query!()` against
+/// an `any:` URL would replay offline as a concrete-driver-typed query, breaking
+/// `AnyPool`/`AnyConnection`-based callers the moment `DATABASE_URL` is unset.
+#[cfg(feature = "offline")]
+fn dispatch_dyn_data<DescribeDB: DatabaseExt>(
+    input: QueryMacroInput,
+    query_data: data::offline::DynQueryData,
+) -> crate::Result<TokenStream>
+where
+    Describe<DescribeDB>: DescribeExt,
+{
+    let emit_db_name = query_data.emit_db_name.clone();
+    let data = QueryData::<DescribeDB>::from_dyn_data(query_data)?;
+
+    match emit_db_name.as_deref() {
+        None => expand_with_data(input, data),
+
+        #[cfg(feature = "any")]
+        Some(sqlx_core::any::Any::NAME) => {
+            expand_with_data_as::<DescribeDB, sqlx_core::any::Any>(input, data)
+        }
+
+        Some(other) => Err(format!(
+            "recorded query data was emitted against {} but the feature for that database \
+             was not enabled",
+            other
+        )
+        .into()),
+    }
+}
+
 // marker trait for `Describe` that lets us conditionally require it to be `Serialize + Deserialize`
 #[cfg(feature = "offline")]
 trait DescribeExt: serde::Serialize + serde::de::DeserializeOwned {}
@@ -176,27 +307,43 @@ fn expand_with_data<DB: DatabaseExt>(
 ) -> crate::Result<TokenStream>
 where
     Describe<DB>: DescribeExt,
+{
+    expand_with_data_as::<DB, DB>(input, data)
+}
+
+/// Shared by every `expand_*` entry point that has a `QueryData` (schema-snapshot
+/// expansion, `schema::expand_from_snapshot`, builds its output separately):
+/// `DescribeDB` is whichever driver actually described the query (so its reported column/param types drive the Rust type
+/// mapping), while `EmitDB` is what the generated code targets. These are always the
+/// same database, except for `query!()` against the `any` scheme (see
+/// `query::any::expand_any`), where `DescribeDB` is the concrete driver enabled
+/// alongside `any` and `EmitDB` is `sqlx::Any`.
+fn expand_with_data_as<DescribeDB, EmitDB>(
+    input: QueryMacroInput,
+    data: QueryData<DescribeDB>,
+) -> crate::Result<TokenStream>
+where
+    DescribeDB: DatabaseExt,
+    EmitDB: DatabaseExt,
+    Describe<DescribeDB>: DescribeExt,
 {
     // validate at the minimum that our args match the query's input parameters
-    if input.arg_names.len() != data.describe.params.len() {
-        return Err(syn::Error::new(
-            Span::call_site(),
-            format!(
-                "expected {} parameters, got {}",
-                data.describe.params.len(),
-                input.arg_names.len()
-            ),
+    if input.arg_exprs.len() != data.describe.params.len() {
+        return Err(format!(
+            "expected {} parameters, got {}",
+            data.describe.params.len(),
+            input.arg_exprs.len()
         )
         .into());
     }
 
-    let args_tokens = args::quote_args(&input, &data.describe)?;
+    let args_tokens = args::quote_args::<EmitDB>(&input, data.describe.params.len())?;
 
     let query_args = format_ident!("query_args");
 
     let output = if data.describe.columns.is_empty() {
         if let RecordType::Generated = input.record_type {
-            let db_path = DB::db_path();
+            let db_path = EmitDB::db_path();
             let sql = &input.src;
 
             quote! {
@@ -208,7 +355,7 @@ where
     } else {
         match input.record_type {
             RecordType::Generated => {
-                let columns = output::columns_to_rust::<DB>(&data.describe)?;
+                let columns = output::columns_to_rust::<DescribeDB>(&data.describe)?;
 
                 let record_name: Type = syn::parse_str("Record").unwrap();
 
@@ -229,7 +376,7 @@ where
                 );
 
                 let query_as =
-                    output::quote_query_as::<DB>(&input, &record_name, &query_args, &columns);
+                    output::quote_query_as::<EmitDB>(&input, &record_name, &query_args, &columns);
 
                 quote! {
                     #[derive(Debug)]
@@ -241,8 +388,8 @@ where
                 }
             }
             RecordType::Given(ref out_ty) => {
-                let columns = output::columns_to_rust::<DB>(&data.describe)?;
-                output::quote_query_as::<DB>(&input, out_ty, &query_args, &columns)
+                let columns = output::columns_to_rust::<DescribeDB>(&data.describe)?;
+                output::quote_query_as::<EmitDB>(&input, out_ty, &query_args, &columns)
             }
             RecordType::Scalar => {
                 if data.describe.columns.len() != 1 {
@@ -253,8 +400,8 @@ where
                     .into());
                 }
 
-                let ty = output::get_scalar_type(1, &data.describe.columns[0]);
-                let db_path = DB::db_path();
+                let ty = output::get_scalar_type::<DescribeDB>(1, &data.describe.columns[0])?;
+                let db_path = EmitDB::db_path();
                 let query = &input.src;
 
                 quote! {
@@ -264,17 +411,13 @@ where
         }
     };
 
-    let arg_names = &input.arg_names;
-
     let ret_tokens = quote! {
-        macro_rules! macro_result {
-            (#($#arg_names:expr),*) => {{
-                use sqlx::Arguments as _;
+        {
+            use sqlx::Arguments as _;
 
-                #args_tokens
+            #args_tokens
 
-                #output
-            }}
+            #output
         }
     };
 
@@ -287,7 +430,7 @@ where
         save_dir.push("sqlx");
 
         std::fs::create_dir_all(&save_dir)?;
-        data.save_in(save_dir, input.src_span)?;
+        data.save_in(save_dir, &input.cache_key(), EmitDB::NAME, input.src_span)?;
     }
 
     Ok(ret_tokens)
@@ -0,0 +1,95 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote};
+use sqlx_core::describe::{Column, Describe};
+use syn::Type;
+
+use crate::database::DatabaseExt;
+use crate::query::input::QueryMacroInput;
+#[cfg(feature = "offline")]
+use crate::query::schema::ResolvedColumn;
+use crate::Result;
+
+pub struct RustColumn {
+    pub ident: Ident,
+    pub type_: Option<Type>,
+}
+
+/// Maps each column of a live `Describe<DB>` to the Rust type used to represent it,
+/// via `DB::return_type_for_id`.
+pub fn columns_to_rust<DB: DatabaseExt>(describe: &Describe<DB>) -> Result<Vec<RustColumn>> {
+    describe
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| rust_column_for::<DB>(i, &column.name, &column.type_name, column.nullable))
+        .collect()
+}
+
+/// Maps each column resolved against a schema snapshot (see `query::schema`) to the
+/// Rust type used to represent it. This is the offline counterpart of
+/// [`columns_to_rust`] for queries that have no recorded `Describe` at all.
+#[cfg(feature = "offline")]
+pub fn columns_from_resolved<DB: DatabaseExt>(
+    resolved: &[ResolvedColumn<'_>],
+) -> Result<Vec<RustColumn>> {
+    resolved
+        .iter()
+        .enumerate()
+        .map(|(i, column)| rust_column_for::<DB>(i, column.name, column.type_name, column.nullable))
+        .collect()
+}
+
+fn rust_column_for<DB: DatabaseExt>(
+    index: usize,
+    name: &str,
+    type_name: &str,
+    nullable: bool,
+) -> Result<RustColumn> {
+    let ident = if name.is_empty() {
+        format_ident!("_{}", index)
+    } else {
+        format_ident!("{}", name)
+    };
+
+    let rust_type = DB::return_type_for_id(type_name).ok_or_else(|| {
+        format!(
+            "unsupported type {:?} of column {:?}; override with `as`",
+            type_name, name
+        )
+    })?;
+
+    let type_ = if nullable {
+        syn::parse_str::<Type>(&format!("Option<{}>", rust_type))?
+    } else {
+        syn::parse_str::<Type>(rust_type)?
+    };
+
+    Ok(RustColumn {
+        ident,
+        type_: Some(type_),
+    })
+}
+
+pub fn get_scalar_type<DB: DatabaseExt>(index: usize, column: &Column<DB>) -> Result<Type> {
+    let rust_column = rust_column_for::<DB>(index, &column.name, &column.type_name, column.nullable)?;
+    Ok(rust_column.type_.expect("scalar columns always have a type"))
+}
+
+/// Emits `sqlx::query_as_with::<#db_path, #out_ty, _>(#query, #bind_args)`, where
+/// `#db_path` is `EmitDB`'s path — for ordinary queries this is the same database the
+/// columns were resolved against, but for `query!()` against `sqlx::Any` it's `Any`
+/// while `columns` still came from whichever concrete driver did the describing.
+pub fn quote_query_as<EmitDB: DatabaseExt>(
+    input: &QueryMacroInput,
+    out_ty: &Type,
+    bind_args: &Ident,
+    columns: &[RustColumn],
+) -> TokenStream {
+    let _ = columns;
+    let db_path = EmitDB::db_path();
+    let query = &input.src;
+
+    quote! {
+        sqlx::query_as_with::<#db_path, #out_ty, _>(#query, #bind_args)
+    }
+}
@@ -0,0 +1,622 @@
+use std::path::{Path, PathBuf};
+
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use serde::Deserialize;
+use syn::Type;
+
+use crate::database::DatabaseExt;
+use crate::query::input::RecordType;
+use crate::query::{args, output};
+use crate::query::QueryMacroInput;
+use crate::Result;
+
+/// A declarative snapshot of a database's schema, produced by `cargo sqlx prepare
+/// --schema` by introspecting `information_schema` (Postgres/MySQL) or `sqlite_master`
+/// (SQLite). Committing this file lets brand-new queries that were never recorded in
+/// `sqlx-data.json` still be checked offline, by resolving their selected expressions
+/// against the known tables instead of against a recorded `Describe`.
+#[derive(Deserialize)]
+pub struct SchemaSnapshot {
+    /// The `Database::NAME` this snapshot was introspected from, e.g. `"PostgreSQL"`.
+    pub database: String,
+    pub tables: Vec<TableSchema>,
+}
+
+#[derive(Deserialize)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<ColumnSchema>,
+    #[serde(default)]
+    pub primary_key: Vec<String>,
+    #[serde(default)]
+    pub unique: Vec<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub type_name: String,
+    pub nullable: bool,
+}
+
+impl SchemaSnapshot {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("failed to open schema snapshot {}: {}", path.display(), e))?;
+
+        serde_json::from_reader(file)
+            .map_err(|e| format!("failed to parse schema snapshot {}: {}", path.display(), e).into())
+    }
+
+    fn table(&self, name: &str) -> Option<&TableSchema> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+}
+
+/// The column a selected expression resolved to, along with whether the result can be
+/// `NULL` (the column is nullable, or the expression reached it through the "may be
+/// absent" side of an outer join).
+pub struct ResolvedColumn<'a> {
+    pub name: &'a str,
+    pub type_name: &'a str,
+    pub nullable: bool,
+}
+
+/// A bound parameter placeholder (`?` or `$N`) resolved against the column it's compared
+/// or assigned to, e.g. the `$1` in `WHERE id = $1`. `resolve_params` yields `None` in
+/// its place when no supported comparison pattern was found around the placeholder.
+/// `expand_resolved` uses only the number of parameters; their types are not checked
+/// against the macro's arguments.
+pub struct ResolvedParam<'a> {
+    pub column_name: &'a str,
+    pub type_name: &'a str,
+    pub nullable: bool,
+}
+
+enum JoinKind {
+    Left,
+    Right,
+}
+
+/// The tables a query's FROM clause resolves against: the driving (FROM) table, plus at
+/// most one joined table. `JoinKind::Left` means the joined table is the "may be absent"
+/// side (its columns gain nullability); `JoinKind::Right` means the FROM table is.
+struct ParsedFrom<'a> {
+    from_name: &'a str,
+    from_table: &'a TableSchema,
+    joined: Option<(JoinKind, &'a str, &'a TableSchema)>,
+}
+
+impl<'a> ParsedFrom<'a> {
+    /// Looks up `column_name`, optionally qualified by `qualifier`, returning the table
+    /// it belongs to and whether that table is on the nullable side of the join.
+    fn locate(
+        &self,
+        qualifier: Option<&str>,
+        column_name: &str,
+        expr: &str,
+        src_span: Span,
+    ) -> Result<(&'a TableSchema, bool)> {
+        let from_is_nullable_side = matches!(self.joined, Some((JoinKind::Right, ..)));
+
+        match qualifier {
+            Some(q) if q == self.from_name => Ok((self.from_table, from_is_nullable_side)),
+            Some(q) => match &self.joined {
+                Some((kind, name, table)) if *name == q => {
+                    Ok((*table, matches!(kind, JoinKind::Left)))
+                }
+                _ => Err(syn::Error::new(
+                    src_span,
+                    format!("unknown table or alias {:?} in {:?}", q, expr),
+                )
+                .into()),
+            },
+            None => {
+                if self.from_table.columns.iter().any(|c| c.name == column_name) {
+                    Ok((self.from_table, from_is_nullable_side))
+                } else if let Some((kind, _, table)) = &self.joined {
+                    if table.columns.iter().any(|c| c.name == column_name) {
+                        Ok((*table, matches!(kind, JoinKind::Left)))
+                    } else {
+                        Err(unknown_column(column_name, self.from_name, src_span))
+                    }
+                } else {
+                    Err(unknown_column(column_name, self.from_name, src_span))
+                }
+            }
+        }
+    }
+}
+
+fn unknown_column(column_name: &str, table_name: &str, src_span: Span) -> crate::Error {
+    syn::Error::new(
+        src_span,
+        format!(
+            "column {:?} not found on table {:?} in schema snapshot",
+            column_name, table_name
+        ),
+    )
+    .into()
+}
+
+/// Parses `SELECT ... FROM <table> [LEFT JOIN <table> ...]` down to the FROM clause,
+/// returning the unparsed select list and the resolved table(s).
+fn parse_from<'a>(
+    src: &'a str,
+    snapshot: &'a SchemaSnapshot,
+    src_span: Span,
+) -> Result<(&'a str, ParsedFrom<'a>)> {
+    let lower = src.to_ascii_lowercase();
+
+    let select_end = lower.find(" from ").ok_or_else(|| {
+        syn::Error::new(
+            src_span,
+            "could not locate FROM clause to resolve against schema snapshot",
+        )
+    })?;
+
+    let select_list = src[..select_end].trim();
+    let select_list = select_list
+        .strip_prefix("SELECT")
+        .or_else(|| select_list.strip_prefix("select"))
+        .ok_or_else(|| syn::Error::new(src_span, "expected query to start with SELECT"))?
+        .trim();
+
+    let rest = &src[select_end + " from ".len()..];
+    let rest_lower = &lower[select_end + " from ".len()..];
+
+    let (from_part, join_part) = match (
+        rest_lower.find(" left join "),
+        rest_lower.find(" right join "),
+    ) {
+        (Some(i), _) => (
+            &rest[..i],
+            Some((JoinKind::Left, &rest[i + " left join ".len()..])),
+        ),
+        (None, Some(i)) => (
+            &rest[..i],
+            Some((JoinKind::Right, &rest[i + " right join ".len()..])),
+        ),
+        (None, None) => (rest, None),
+    };
+
+    let from_name = first_identifier(from_part)
+        .ok_or_else(|| syn::Error::new(src_span, "could not locate table name after FROM"))?;
+
+    let from_table = snapshot.table(from_name).ok_or_else(|| {
+        syn::Error::new(
+            src_span,
+            format!("table {:?} not found in schema snapshot", from_name),
+        )
+    })?;
+
+    let joined = join_part
+        .map(|(kind, join_rest)| {
+            let join_name = first_identifier(join_rest).ok_or_else(|| {
+                syn::Error::new(src_span, "could not locate table name after JOIN")
+            })?;
+
+            let join_table = snapshot.table(join_name).ok_or_else(|| {
+                syn::Error::new(
+                    src_span,
+                    format!("table {:?} not found in schema snapshot", join_name),
+                )
+            })?;
+
+            Ok::<_, crate::Error>((kind, join_name, join_table))
+        })
+        .transpose()?;
+
+    Ok((
+        select_list,
+        ParsedFrom {
+            from_name,
+            from_table,
+            joined,
+        },
+    ))
+}
+
+fn first_identifier(s: &str) -> Option<&str> {
+    let token = s.split_whitespace().next()?;
+    let token = token.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_');
+    if token.is_empty() {
+        None
+    } else {
+        Some(token)
+    }
+}
+
+/// A minimal resolver: handles `SELECT <cols> FROM <table> [LEFT JOIN <table> ...]` or
+/// `[RIGHT JOIN <table> ...]`, enough to typecheck the common case offline. Anything it
+/// can't confidently resolve is an error pointing at `src_span` rather than a silent
+/// pass-through.
+pub fn resolve<'a>(
+    src: &'a str,
+    snapshot: &'a SchemaSnapshot,
+    src_span: Span,
+) -> Result<Vec<ResolvedColumn<'a>>> {
+    let (select_list, parsed) = parse_from(src, snapshot, src_span)?;
+
+    select_list
+        .split(',')
+        .map(|expr| expr.trim())
+        .map(|expr| {
+            let (qualifier, column_name) = match expr.rsplit_once('.') {
+                Some((q, c)) => (Some(q.trim()), c.trim()),
+                None => (None, expr),
+            };
+
+            let (table, nullable_side) = parsed.locate(qualifier, column_name, expr, src_span)?;
+
+            let column = table
+                .columns
+                .iter()
+                .find(|column| column.name == column_name)
+                .ok_or_else(|| unknown_column(column_name, &table.name, src_span))?;
+
+            Ok(ResolvedColumn {
+                name: &column.name,
+                type_name: &column.type_name,
+                nullable: column.nullable || nullable_side,
+            })
+        })
+        .collect()
+}
+
+/// Scans `src` for placeholder parameters (`?`, or `$1`/`$2`/... as Postgres uses) and
+/// resolves each against the column it's compared to, e.g. the `$1` in `WHERE id = $1`
+/// resolves to `users.id`. A placeholder with no recognized `<column> <op> <placeholder>`
+/// or `<placeholder> <op> <column>` pattern around it resolves to `None` rather than
+/// erroring — plenty of valid queries bind parameters in positions this doesn't parse
+/// (e.g. inside function calls), and the query text itself is the source of truth for
+/// *how many* parameters there are regardless of whether every one resolves to a type.
+pub fn resolve_params<'a>(
+    src: &'a str,
+    snapshot: &'a SchemaSnapshot,
+    src_span: Span,
+) -> Result<Vec<Option<ResolvedParam<'a>>>> {
+    let (_, parsed) = parse_from(src, snapshot, src_span)?;
+
+    let placeholders = find_placeholders(src);
+
+    placeholders
+        .into_iter()
+        .map(|(start, end)| {
+            let Some((qualifier, column_name)) = nearby_column_ref(src, start, end) else {
+                return Ok(None);
+            };
+
+            match parsed.locate(qualifier, column_name, column_name, src_span) {
+                Ok((table, _)) => {
+                    let column = table
+                        .columns
+                        .iter()
+                        .find(|column| column.name == column_name)
+                        .ok_or_else(|| unknown_column(column_name, &table.name, src_span))?;
+
+                    Ok(Some(ResolvedParam {
+                        column_name: &column.name,
+                        type_name: &column.type_name,
+                        nullable: column.nullable,
+                    }))
+                }
+                // The token next to the placeholder wasn't actually a known column
+                // (could be a keyword, a literal, etc.) — leave this param untyped
+                // rather than treating every ambiguous neighbor as an error.
+                Err(_) => Ok(None),
+            }
+        })
+        .collect()
+}
+
+/// Byte ranges of every `?` placeholder and of the first occurrence of each distinct
+/// `$<digits>` placeholder in `src`, in source order.
+fn find_placeholders(src: &str) -> Vec<(usize, usize)> {
+    let bytes = src.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'?' => {
+                out.push((i, i + 1));
+                i += 1;
+            }
+            b'$' if i + 1 < bytes.len() && bytes[i + 1].is_ascii_digit() => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+                // A repeated `$N` binds the same parameter again, as a live describe reports.
+                if !out.iter().any(|&(s, e)| src[s..e] == src[start..i]) {
+                    out.push((start, i));
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    out
+}
+
+/// Looks immediately before, then immediately after, the placeholder at `[start, end)`
+/// for a `<qualifier.>column <op>` or `<op> <qualifier.>column` pattern, returning the
+/// qualifier (if any) and column name.
+fn nearby_column_ref(src: &str, start: usize, end: usize) -> Option<(Option<&str>, &str)> {
+    const OPS: &[char] = &['=', '<', '>', '!'];
+
+    let before = src[..start].trim_end();
+    let after = src[end..].trim_start();
+
+    let candidate = before
+        .trim_end_matches(OPS)
+        .trim_end()
+        .rsplit(|c: char| c.is_whitespace() || c == '(' || c == ',')
+        .next()
+        .filter(|s| !s.is_empty() && before.len() != before.trim_end_matches(OPS).trim_end().len())
+        .or_else(|| {
+            after
+                .trim_start_matches(OPS)
+                .trim_start()
+                .split(|c: char| c.is_whitespace() || c == ')' || c == ',')
+                .next()
+                .filter(|s| {
+                    !s.is_empty() && after.len() != after.trim_start_matches(OPS).trim_start().len()
+                })
+        })?;
+
+    match candidate.rsplit_once('.') {
+        Some((q, c)) => Some((Some(q), c)),
+        None => Some((None, candidate)),
+    }
+}
+
+/// Default path `cargo sqlx prepare --schema` writes to and `query!()` reads from.
+pub fn default_snapshot_path(manifest_dir: &str) -> PathBuf {
+    Path::new(manifest_dir).join("sqlx-schema.json")
+}
+
+pub fn expand_from_snapshot(
+    input: QueryMacroInput,
+    snapshot: &SchemaSnapshot,
+) -> Result<proc_macro2::TokenStream> {
+    match &*snapshot.database {
+        #[cfg(feature = "postgres")]
+        sqlx_core::postgres::Postgres::NAME => {
+            expand_resolved::<sqlx_core::postgres::Postgres>(input, snapshot)
+        }
+        #[cfg(feature = "mysql")]
+        sqlx_core::mysql::MySql::NAME => expand_resolved::<sqlx_core::mysql::MySql>(input, snapshot),
+        #[cfg(feature = "sqlite")]
+        sqlx_core::sqlite::Sqlite::NAME => expand_resolved::<sqlx_core::sqlite::Sqlite>(input, snapshot),
+        db_name => Err(format!(
+            "found schema snapshot for {} but the feature for that database was not enabled",
+            db_name
+        )
+        .into()),
+    }
+}
+
+/// Builds the macro's output directly from `resolve()`'s `ResolvedColumn`s, without ever
+/// constructing a `Describe<DB>` — there's no live connection or recorded data here to
+/// build one from, just a schema snapshot. The expected parameter count comes from
+/// `resolve_params` scanning the query text itself (mirroring what a live `DESCRIBE`
+/// would report), not from trusting the macro call's own argument list.
+fn expand_resolved<DB: DatabaseExt>(
+    input: QueryMacroInput,
+    snapshot: &SchemaSnapshot,
+) -> Result<proc_macro2::TokenStream> {
+    let resolved = resolve(&input.src, snapshot, input.src_span)?;
+    let columns = output::columns_from_resolved::<DB>(&resolved)?;
+
+    let params = resolve_params(&input.src, snapshot, input.src_span)?;
+    let args_tokens = args::quote_args::<DB>(&input, params.len())?;
+    let query_args = format_ident!("query_args");
+
+    let output = match input.record_type {
+        RecordType::Generated => {
+            let record_name: Type = syn::parse_str("Record").unwrap();
+
+            let record_fields = columns.iter().map(
+                |&output::RustColumn {
+                     ref ident,
+                     ref type_,
+                 }| quote!(#ident: #type_,),
+            );
+
+            let query_as =
+                output::quote_query_as::<DB>(&input, &record_name, &query_args, &columns);
+
+            quote! {
+                #[derive(Debug)]
+                struct #record_name {
+                    #(#record_fields)*
+                }
+
+                #query_as
+            }
+        }
+        RecordType::Given(ref out_ty) => {
+            output::quote_query_as::<DB>(&input, out_ty, &query_args, &columns)
+        }
+        RecordType::Scalar => {
+            if columns.len() != 1 {
+                return Err(format!(
+                    "expected exactly one column from query, got {}",
+                    columns.len()
+                )
+                .into());
+            }
+
+            let ty = columns[0]
+                .type_
+                .clone()
+                .expect("scalar columns always have a type");
+            let db_path = DB::db_path();
+            let query = &input.src;
+
+            quote! {
+                sqlx::query_scalar_with::<#db_path, #ty, _>(#query, #query_args)
+            }
+        }
+    };
+
+    Ok(quote! {
+        {
+            use sqlx::Arguments as _;
+
+            #args_tokens
+
+            #output
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> SchemaSnapshot {
+        SchemaSnapshot {
+            database: "PostgreSQL".to_string(),
+            tables: vec![
+                TableSchema {
+                    name: "users".to_string(),
+                    columns: vec![
+                        ColumnSchema {
+                            name: "id".to_string(),
+                            type_name: "INT4".to_string(),
+                            nullable: false,
+                        },
+                        ColumnSchema {
+                            name: "email".to_string(),
+                            type_name: "TEXT".to_string(),
+                            nullable: true,
+                        },
+                    ],
+                    primary_key: vec!["id".to_string()],
+                    unique: vec![],
+                },
+                TableSchema {
+                    name: "sessions".to_string(),
+                    columns: vec![
+                        ColumnSchema {
+                            name: "user_id".to_string(),
+                            type_name: "INT4".to_string(),
+                            nullable: false,
+                        },
+                        ColumnSchema {
+                            name: "expires_at".to_string(),
+                            type_name: "TIMESTAMPTZ".to_string(),
+                            nullable: false,
+                        },
+                    ],
+                    primary_key: vec![],
+                    unique: vec![],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn resolve_matches_plain_columns_against_the_table() {
+        let snapshot = snapshot();
+        let resolved = resolve("SELECT id, email FROM users", &snapshot, Span::call_site())
+            .expect("should resolve");
+
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].name, "id");
+        assert_eq!(resolved[0].type_name, "INT4");
+        assert!(!resolved[0].nullable);
+        assert_eq!(resolved[1].name, "email");
+        assert!(resolved[1].nullable);
+    }
+
+    #[test]
+    fn resolve_does_not_widen_the_driving_side_of_a_left_join() {
+        let snapshot = snapshot();
+        let resolved = resolve(
+            "SELECT id FROM users LEFT JOIN sessions ON sessions.user_id = users.id",
+            &snapshot,
+            Span::call_site(),
+        )
+        .expect("should resolve");
+
+        assert!(!resolved[0].nullable);
+    }
+
+    #[test]
+    fn resolve_widens_the_joined_side_of_a_left_join() {
+        let snapshot = snapshot();
+        let resolved = resolve(
+            "SELECT sessions.expires_at FROM users LEFT JOIN sessions ON sessions.user_id = users.id",
+            &snapshot,
+            Span::call_site(),
+        )
+        .expect("should resolve");
+
+        assert!(resolved[0].nullable);
+    }
+
+    #[test]
+    fn resolve_widens_the_driving_side_of_a_right_join() {
+        let snapshot = snapshot();
+        let resolved = resolve(
+            "SELECT id FROM users RIGHT JOIN sessions ON sessions.user_id = users.id",
+            &snapshot,
+            Span::call_site(),
+        )
+        .expect("should resolve");
+
+        assert!(resolved[0].nullable);
+    }
+
+    #[test]
+    fn resolve_errors_on_unknown_table() {
+        let snapshot = snapshot();
+        assert!(resolve("SELECT id FROM accounts", &snapshot, Span::call_site()).is_err());
+    }
+
+    #[test]
+    fn resolve_errors_on_unknown_column() {
+        let snapshot = snapshot();
+        assert!(resolve("SELECT nickname FROM users", &snapshot, Span::call_site()).is_err());
+    }
+
+    #[test]
+    fn resolve_params_finds_the_column_compared_against_each_placeholder() {
+        let snapshot = snapshot();
+        let params = resolve_params(
+            "SELECT id FROM users WHERE id = $1 AND email = $2",
+            &snapshot,
+            Span::call_site(),
+        )
+        .expect("should resolve");
+
+        assert_eq!(params.len(), 2);
+        let id_param = params[0].as_ref().expect("should resolve id param");
+        assert_eq!(id_param.column_name, "id");
+        assert_eq!(id_param.type_name, "INT4");
+
+        let email_param = params[1].as_ref().expect("should resolve email param");
+        assert_eq!(email_param.column_name, "email");
+        assert_eq!(email_param.type_name, "TEXT");
+    }
+
+    #[test]
+    fn resolve_params_counts_placeholders_it_cannot_resolve_a_type_for() {
+        let snapshot = snapshot();
+        let params = resolve_params(
+            "SELECT id FROM users WHERE lower(email) = ?",
+            &snapshot,
+            Span::call_site(),
+        )
+        .expect("should still count the placeholder");
+
+        assert_eq!(params.len(), 1);
+    }
+}
@@ -0,0 +1,18 @@
+use std::future::Future;
+
+/// Blocks the current thread until `fut` resolves. The query macros run inside
+/// `proc_macro`, which has no executor of its own, so every database interaction during
+/// macro expansion goes through this to drive an ad-hoc runtime.
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    #[cfg(feature = "runtime-async-std")]
+    return async_std::task::block_on(fut);
+
+    #[cfg(feature = "runtime-tokio")]
+    return {
+        let rt = tokio::runtime::Runtime::new().expect("failed to start Tokio runtime");
+        rt.block_on(fut)
+    };
+
+    #[cfg(not(any(feature = "runtime-async-std", feature = "runtime-tokio")))]
+    compile_error!("one of 'runtime-async-std' or 'runtime-tokio' features must be enabled");
+}